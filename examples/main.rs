@@ -23,10 +23,7 @@ use actix_web::{
     web::{self, Bytes, BytesMut},
     App, Error, FromRequest, HttpMessage, HttpResponse, HttpServer, Responder,
 };
-use actix_web_detached_jws_middleware::{
-    buffering::{enable_request_buffering, FileBufferingStreamBuilder},
-    verify::{DetachedJwsConfig, DetachedJwsVerify, ShouldVerify, VerifyErrorType},
-};
+use actix_web_detached_jws_middleware::verify::{DetachedJwsVerify, DetachedJwsVerifyConfig, VerifyErrorType};
 use detached_jws::{JwsHeader, Verify};
 use executor::block_on_stream;
 use futures::{
@@ -72,9 +69,8 @@ impl Config {
     }
 }
 
-impl<'a> DetachedJwsConfig<'a> for Config {
+impl<'a> DetachedJwsVerifyConfig<'a> for Config {
     type Verifier = Verifier<'a>;
-    type ShouldVerify = LocalBoxFuture<'a, ShouldVerify>;
     type ErrorHandler = Ready<Error>;
 
     fn get_verifier(&'a self, h: &JwsHeader) -> Option<Self::Verifier> {
@@ -95,48 +91,26 @@ impl<'a> DetachedJwsConfig<'a> for Config {
         }
     }
 
-    fn should_verify(&'a self, req: &'a mut ServiceRequest) -> Self::ShouldVerify {
-        async move {
-            let mut builder = FileBufferingStreamBuilder::new();
-
-            enable_request_buffering(builder, req);
-
-            /*
-            {
-                let mut body = BytesMut::new();
-
-                while let Some(chunk) = stream.next().await {
-                    body.extend_from_slice(&chunk.unwrap());
-                }
-
-                //println!("request body: {:?}", body);
-            }*/
-
-            { /*
-                 let mut body = BytesMut::new();
-
-                 while let Some(chunk) = stream.next().await {
-                     body.extend_from_slice(&chunk.unwrap());
-                 }*/
-
-                //println!("request body: {:?}", body);
-            }
-
-            true
-        }
-        .boxed_local()
-
-        //ready(req.headers().contains_key("X-JWS-Signature"))
+    fn should_verify(&'a self, _req: &'a mut ServiceRequest) -> LocalBoxFuture<'a, bool> {
+        // `DetachedJwsVerify`'s middleware already buffers `req`'s payload before
+        // calling this and hands out an independent `BufferedBodyReader`
+        // clone, so reading it here wouldn't disturb the JWS check's own
+        // read; nothing here needs to read it today.
+        ready(true).boxed_local()
     }
 
     fn error_handler(
         &'a self,
-        req: &'a mut ServiceRequest,
+        _req: &'a mut ServiceRequest,
         error: VerifyErrorType,
     ) -> Self::ErrorHandler {
         ready(match error {
             VerifyErrorType::HeaderNotFound => ErrorForbidden("Header Not Found"),
             VerifyErrorType::IncorrectSignature => ErrorForbidden("Incorrect Signature"),
+            VerifyErrorType::DigestMismatch => ErrorForbidden("Digest Mismatch"),
+            VerifyErrorType::UnsupportedAlgorithm(alg) => {
+                ErrorForbidden(format!("Unsupported Algorithm: {}", alg))
+            }
             VerifyErrorType::Other(e) => ErrorForbidden(e),
         })
     }
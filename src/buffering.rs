@@ -1,13 +1,20 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
+    io::SeekFrom,
     path::{Path, PathBuf},
-    pin::{self, Pin},
+    pin::Pin,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 
-use actix_web::{HttpMessage, dev::{BodySize, MessageBody, Payload, ServiceRequest}, error::PayloadError, http::{HeaderName, HeaderValue}, web::{Bytes, BytesMut}};
+use actix_web::{
+    dev::{Body, BodySize, MessageBody, Payload, ResponseBody, ServiceRequest, ServiceResponse},
+    error::PayloadError,
+    http::{HeaderName, HeaderValue},
+    web::{Bytes, BytesMut},
+    Error, HttpMessage,
+};
 use futures::{ready, Stream, StreamExt};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite};
 use uuid::Uuid;
 
 pub fn enable_request_buffering<T>(builder: T, req: &mut ServiceRequest)
@@ -27,11 +34,98 @@ where
     }
 }
 
+/// Tees a response body through the same memory-first/spill-to-disk buffer
+/// used for requests, so a sign/digest middleware can read the body once and
+/// still forward it downstream unchanged.
+pub fn enable_response_buffering<T>(
+    builder: T,
+    mut res: ServiceResponse<Body>,
+) -> ServiceResponse<FileBufferingStream<ResponseBody<Body>>>
+where
+    T: AsRef<FileBufferingStreamBuilder>,
+{
+    let body = res.take_body();
+    res.map_body(|_, _| ResponseBody::Body(builder.as_ref().build(body)))
+}
+
+/// Pulls the next chunk out of a `MessageBody`. Response bodies (unlike
+/// request payloads) only implement `MessageBody`, not `Stream`, so a
+/// sign/digest middleware drains one via this instead of `StreamExt::next`.
+pub async fn next_body_chunk<B>(body: &mut B) -> Option<Result<Bytes, Error>>
+where
+    B: MessageBody + Unpin,
+{
+    futures::future::poll_fn(|cx| MessageBody::poll_next(Pin::new(body), cx)).await
+}
+
+/// Which async I/O backend a spilled buffer uses once it crosses
+/// `threshold`. `Tokio` (the default) goes through `tokio::fs::File`; on
+/// Linux, `IoUring` goes through the `rio` crate instead, avoiding a thread
+/// pool hop per operation at the cost of being Linux-only.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileBackend {
+    Tokio,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    IoUring,
+}
+
+impl Default for FileBackend {
+    fn default() -> Self {
+        FileBackend::Tokio
+    }
+}
+
+/// A free list of reusable scratch buffers sized to `produce_block_size`,
+/// shared (via `Arc`) by every stream a builder produces. The file-replay
+/// path checks a buffer out, reads a block into it, copies that block into
+/// the `Bytes` handed to the caller, then returns the buffer to the list
+/// instead of letting it drop — so a long-lived pool amortizes away the
+/// `vec![0u8; block_size]` allocation that used to happen on every produced
+/// block. If the list is empty a buffer is allocated directly, so
+/// correctness never depends on the pool having room.
+#[derive(Clone)]
+struct BytePool {
+    free: Arc<Mutex<Vec<Vec<u8>>>>,
+    capacity: usize,
+}
+
+impl BytePool {
+    fn new(capacity: usize) -> Self {
+        Self {
+            free: Arc::new(Mutex::new(Vec::new())),
+            capacity,
+        }
+    }
+
+    fn checkout(&self, len: usize) -> Vec<u8> {
+        let mut buf = self
+            .free
+            .lock()
+            .expect("byte pool mutex poisoned")
+            .pop()
+            .unwrap_or_default();
+        buf.clear();
+        buf.resize(len, 0);
+        buf
+    }
+
+    fn recycle(&self, mut buf: Vec<u8>) {
+        let mut free = self.free.lock().expect("byte pool mutex poisoned");
+        if free.len() < self.capacity {
+            buf.clear();
+            free.push(buf);
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct FileBufferingStreamBuilder {
     tmp_dir: PathBuf,
     threshold: usize,
     produce_block_size: usize,
     buffer_limit: Option<usize>,
+    file_backend: FileBackend,
+    pool: BytePool,
 }
 
 impl FileBufferingStreamBuilder {
@@ -41,6 +135,8 @@ impl FileBufferingStreamBuilder {
             threshold: 1024 * 30,
             produce_block_size: 1024 * 30,
             buffer_limit: None,
+            file_backend: FileBackend::default(),
+            pool: BytePool::new(16),
         }
     }
 
@@ -64,6 +160,20 @@ impl FileBufferingStreamBuilder {
         self
     }
 
+    /// Selects the async I/O backend used once a buffer spills to disk.
+    pub fn file_backend(mut self, v: FileBackend) -> Self {
+        self.file_backend = v;
+        self
+    }
+
+    /// Sets how many replay scratch buffers the shared pool keeps around for
+    /// reuse. Replaces the pool outright, so set this before building any
+    /// streams that should share it.
+    pub fn pool_capacity(mut self, v: usize) -> Self {
+        self.pool = BytePool::new(v);
+        self
+    }
+
     pub fn build<S>(&self, inner: S) -> FileBufferingStream<S> {
         FileBufferingStream::new(
             inner,
@@ -71,6 +181,8 @@ impl FileBufferingStreamBuilder {
             self.threshold,
             self.produce_block_size,
             self.buffer_limit,
+            self.file_backend,
+            self.pool.clone(),
         )
     }
 }
@@ -81,9 +193,285 @@ impl AsRef<FileBufferingStreamBuilder> for FileBufferingStreamBuilder {
     }
 }
 
+/// An open spill file plus whichever async backend is driving it.
+enum AsyncFile {
+    Tokio(tokio::fs::File),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    IoUring(io_uring_backend::IoUringFile),
+}
+
+impl AsyncFile {
+    fn open_sync(path: &Path) -> std::io::Result<std::fs::File> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create_new(true)
+            .open(path)
+    }
+
+    /// Opens an already-spilled buffer file for reading, e.g. so an
+    /// independent `BufferedBodyReader` can replay it with its own handle
+    /// and cursor.
+    fn open_read(path: &Path) -> std::io::Result<std::fs::File> {
+        std::fs::OpenOptions::new().read(true).open(path)
+    }
+
+    fn from_std(file: std::fs::File, backend: FileBackend) -> std::io::Result<Self> {
+        match backend {
+            FileBackend::Tokio => Ok(AsyncFile::Tokio(tokio::fs::File::from_std(file))),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            FileBackend::IoUring => Ok(AsyncFile::IoUring(io_uring_backend::IoUringFile::new(file)?)),
+        }
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match self {
+            AsyncFile::Tokio(f) => Pin::new(f).poll_write(cx, buf),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            AsyncFile::IoUring(f) => f.poll_write(cx, buf),
+        }
+    }
+
+    /// Writes as much of `bufs` as the backend takes in one call. Neither
+    /// backend issues a real `writev`: tokio 0.2's `AsyncWrite` has no
+    /// `poll_write_vectored` of its own, so the `Tokio` backend writes just
+    /// the first non-empty slice — the same fallback the `io-uring` backend
+    /// below uses. A memory-to-file spill (or a batch of queued chunks)
+    /// therefore still costs one write per buffer.
+    fn poll_write_vectored(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        match self {
+            AsyncFile::Tokio(f) => {
+                let buf = bufs.iter().find(|b| !b.is_empty()).map_or(&[][..], |b| &b[..]);
+                Pin::new(f).poll_write(cx, buf)
+            }
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            AsyncFile::IoUring(f) => f.poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        match self {
+            AsyncFile::Tokio(f) => Pin::new(f).poll_read(cx, buf),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            AsyncFile::IoUring(f) => f.poll_read(cx, buf),
+        }
+    }
+
+    fn poll_rewind(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self {
+            AsyncFile::Tokio(f) => {
+                ready!(Pin::new(&mut *f).start_seek(cx, SeekFrom::Start(0)))?;
+                ready!(Pin::new(f).poll_complete(cx))?;
+                Poll::Ready(Ok(()))
+            }
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            AsyncFile::IoUring(f) => f.poll_rewind(cx),
+        }
+    }
+}
+
+/// Builds the `IoSlice` array for a vectored write starting `skip` bytes
+/// into the logical concatenation of `bufs`, dropping already-written
+/// leading buffers (and trimming a partially-written one).
+fn io_slices_from(bufs: &[Bytes], mut skip: usize) -> Vec<std::io::IoSlice<'_>> {
+    let mut slices = Vec::with_capacity(bufs.len());
+    for b in bufs {
+        if skip >= b.len() {
+            skip -= b.len();
+            continue;
+        }
+        slices.push(std::io::IoSlice::new(&b[skip..]));
+        skip = 0;
+    }
+    slices
+}
+
+/// Drives a `poll_write_vectored`-style call until every slice in `bufs`
+/// has been written in full, tracking progress in `offset` across repeated
+/// `Poll::Pending` wake-ups. `IoSlice` borrows, so the slice array is
+/// rebuilt on each poll rather than kept across wake-ups.
+fn poll_write_all_vectored(
+    file: &mut AsyncFile,
+    cx: &mut Context<'_>,
+    bufs: &[Bytes],
+    offset: &mut usize,
+) -> Poll<std::io::Result<()>> {
+    let total: usize = bufs.iter().map(|b| b.len()).sum();
+    while *offset < total {
+        let slices = io_slices_from(bufs, *offset);
+        let n = ready!(file.poll_write_vectored(cx, &slices))?;
+        if n == 0 {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::WriteZero,
+                "failed to write whole buffer",
+            )));
+        }
+        *offset += n;
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Drives a `poll_read`-style call until `buf` has been filled in full.
+fn poll_read_exact(
+    file: &mut AsyncFile,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+    filled: &mut usize,
+) -> Poll<std::io::Result<()>> {
+    while *filled < buf.len() {
+        let n = ready!(file.poll_read(cx, &mut buf[*filled..]))?;
+        if n == 0 {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "unexpected end of buffering file",
+            )));
+        }
+        *filled += n;
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Drives a spill file through `rio`'s io_uring completions instead of
+/// `tokio::fs::File`'s blocking-pool thread per operation. Linux-only and
+/// opt-in via the `io-uring` feature.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod io_uring_backend {
+    use std::{
+        fs::File,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use futures::Future;
+    use once_cell::sync::Lazy;
+
+    static RING: Lazy<rio::Rio> = Lazy::new(|| rio::new().expect("failed to start io_uring"));
+
+    type WriteOp = Pin<Box<dyn Future<Output = std::io::Result<usize>> + Send>>;
+    type ReadOp = Pin<Box<dyn Future<Output = std::io::Result<(Vec<u8>, usize)>> + Send>>;
+
+    pub struct IoUringFile {
+        file: Arc<File>,
+        pos: u64,
+        write_op: Option<WriteOp>,
+        read_op: Option<ReadOp>,
+    }
+
+    impl IoUringFile {
+        pub fn new(file: File) -> std::io::Result<Self> {
+            Ok(Self {
+                file: Arc::new(file),
+                pos: 0,
+                write_op: None,
+                read_op: None,
+            })
+        }
+
+        pub fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            if self.write_op.is_none() {
+                let file = self.file.clone();
+                let owned = buf.to_vec();
+                let pos = self.pos;
+                self.write_op = Some(Box::pin(async move { RING.write_at(&*file, &owned, pos).await }));
+            }
+
+            match self.write_op.as_mut().expect("checked above").as_mut().poll(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(result) => {
+                    self.write_op = None;
+                    if let Ok(n) = result {
+                        self.pos += n as u64;
+                    }
+                    Poll::Ready(result)
+                }
+            }
+        }
+
+        /// `rio` has no writev wiring yet, so this falls back to writing
+        /// just the first non-empty slice, matching the documented fallback
+        /// behavior of `AsyncWrite::poll_write_vectored`'s default impl.
+        pub fn poll_write_vectored(
+            &mut self,
+            cx: &mut Context<'_>,
+            bufs: &[std::io::IoSlice<'_>],
+        ) -> Poll<std::io::Result<usize>> {
+            let buf = bufs.iter().find(|b| !b.is_empty()).map_or(&[][..], |b| &b[..]);
+            self.poll_write(cx, buf)
+        }
+
+        pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            if self.read_op.is_none() {
+                let file = self.file.clone();
+                let pos = self.pos;
+                let len = buf.len();
+                self.read_op = Some(Box::pin(async move {
+                    let scratch = vec![0u8; len];
+                    let n = RING.read_at(&*file, &scratch, pos).await?;
+                    Ok((scratch, n))
+                }));
+            }
+
+            match self.read_op.as_mut().expect("checked above").as_mut().poll(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(Ok((scratch, n))) => {
+                    self.read_op = None;
+                    buf[..n].copy_from_slice(&scratch[..n]);
+                    self.pos += n as u64;
+                    Poll::Ready(Ok(n))
+                }
+                Poll::Ready(Err(e)) => {
+                    self.read_op = None;
+                    Poll::Ready(Err(e))
+                }
+            }
+        }
+
+        pub fn poll_rewind(&mut self, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            self.pos = 0;
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+/// Owns a spill file's path and removes it once the last handle referencing
+/// it is dropped. Shared via `Arc` so a `BufferedBodyReader` tee'd off a
+/// `FileBufferingStream` can keep the file alive past the original stream's
+/// lifetime.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let path = self.0.clone();
+        // `Drop` can't be async, so the removal is submitted to actix-web's
+        // own blocking thread pool and the resulting future
+        // is left unawaited — `actix_threadpool::run` hands the closure to
+        // the pool immediately, so the removal still happens, we just don't
+        // wait around to see it finish.
+        let _ = actix_threadpool::run(move || -> Result<(), ()> {
+            if let Err(e) = std::fs::remove_file(&path) {
+                println!("error at remove buffering file {:?}. {}", path, e);
+            }
+            Ok(())
+        });
+    }
+}
+
 enum Buffer {
     Memory(BytesMut),
-    File(PathBuf, File),
+    File(Arc<TempFile>, AsyncFile),
+}
+
+/// Tracks an async file operation in progress across repeated polls, since a
+/// `poll_next` call can't simply `.await` one.
+enum PendingOp {
+    Write { bufs: Vec<Bytes>, offset: usize },
+    Rewind,
+    Read { buf: Vec<u8>, filled: usize },
 }
 
 pub struct FileBufferingStream<S> {
@@ -94,22 +482,32 @@ pub struct FileBufferingStream<S> {
     threshold: usize,
     produce_block_size: usize,
     buffer_limit: Option<usize>,
+    file_backend: FileBackend,
+    pool: BytePool,
 
     buffer: Buffer,
     buffer_size: usize,
     produce_index: usize,
-}
-
-impl<S> Drop for FileBufferingStream<S> {
-    fn drop(&mut self) {
-        match self.buffer {
-            Buffer::Memory(_) => {}
-            Buffer::File(ref path, _) => match std::fs::remove_file(path) {
-                Ok(_) => {}
-                Err(e) => println!("error at remove buffering file {:?}. {}", path, e),
-            },
-        };
-    }
+    pending: Option<PendingOp>,
+    /// Chunks pulled from `inner` this round whose write to the buffer is
+    /// still in flight; kept here so a woken `poll_next` resumes that write
+    /// instead of re-polling `inner` for new chunks. Also doubles as the
+    /// batch being assembled: several chunks may land here before a write
+    /// is even attempted, so the eventual buffer write covers all of them.
+    pending_chunks: Vec<Bytes>,
+    /// Set once a chunk would push `buffer_size` past `buffer_limit`, but
+    /// chunks collected earlier in the same batch still need to be emitted
+    /// first. Consumed (and turned into `overflowed`) on the next call with
+    /// nothing left to batch.
+    pending_overflow: bool,
+    /// Set once the overflow error has been surfaced. Terminal: every poll
+    /// after that returns `None` instead of resuming the stream.
+    overflowed: bool,
+    /// Set by `rewind()` once `inner` has been fully drained, requesting one
+    /// more pass over the buffer from the start. Cleared again once that
+    /// pass reaches its own end, so a `poll_next` call never starts a replay
+    /// on its own — only a deliberate `rewind()` does.
+    replaying: bool,
 }
 
 impl<S> FileBufferingStream<S> {
@@ -119,104 +517,275 @@ impl<S> FileBufferingStream<S> {
         threshold: usize,
         produce_block_size: usize,
         buffer_limit: Option<usize>,
+        file_backend: FileBackend,
+        pool: BytePool,
     ) -> Self {
         Self {
-            inner: inner,
+            inner,
             inner_eof: false,
 
             tmp_dir,
             threshold,
             produce_block_size,
-            buffer_limit: buffer_limit,
+            buffer_limit,
+            file_backend,
+            pool,
 
             buffer: Buffer::Memory(BytesMut::new()),
             buffer_size: 0,
             produce_index: 0,
+            pending: None,
+            pending_chunks: Vec::new(),
+            pending_overflow: false,
+            overflowed: false,
+            replaying: false,
+        }
+    }
+
+    /// Restarts consumption of the already-buffered payload from the
+    /// beginning. A no-op until the inner source has been fully drained,
+    /// since there is nothing buffered yet to replay. Unlike the automatic
+    /// EOF-to-replay transition this type used to make on its own, a replay
+    /// pass only ever starts because of an explicit `rewind()` call.
+    pub fn rewind(&mut self) {
+        if self.inner_eof {
+            self.produce_index = 0;
+            self.pending = None;
+            self.replaying = true;
         }
     }
 
-    fn write_to_buffer(&mut self, bytes: &Bytes) -> Result<(), std::io::Error> {
-        match self.buffer {
-            Buffer::Memory(ref mut memory) => {
-                if self.threshold < memory.len() + bytes.len() {
-                    let mut path = self.tmp_dir.to_path_buf();
-                    path.push(Uuid::new_v4().to_simple().to_string());
+    /// Tees off an independent, cheaply cloneable reader over the payload
+    /// this stream has fully buffered, so e.g. a signature validator and the
+    /// downstream handler can each consume the same request body without
+    /// re-reading `inner`. Returns `None` if `inner` hasn't reached EOF yet,
+    /// since there is nothing complete to hand out.
+    pub fn into_reader(self) -> Option<BufferedBodyReader> {
+        if !self.inner_eof {
+            return None;
+        }
 
-                    let mut file = OpenOptions::new()
-                        .write(true)
-                        .read(true)
-                        .create_new(true)
-                        .open(&path)?;
+        let data = match self.buffer {
+            Buffer::Memory(memory) => BufferedData::Memory(memory.freeze()),
+            Buffer::File(temp_file, _) => BufferedData::File(temp_file),
+        };
 
-                    file.write_all(&memory[..])?;
-                    file.write_all(bytes)?;
+        Some(BufferedBodyReader {
+            data,
+            buffer_size: self.buffer_size,
+            produce_block_size: self.produce_block_size,
+            file_backend: self.file_backend,
+            pool: self.pool,
+            produce_index: 0,
+            file: None,
+            pending: None,
+        })
+    }
 
-                    self.buffer = Buffer::File(path, file);
-                } else {
-                    memory.extend_from_slice(bytes)
-                }
+    /// Checks whether writing `additional` more bytes would push
+    /// `buffer_size` past `buffer_limit`, modeled on http-body's `Limited`.
+    /// Must be called *before* the chunk is written to the buffer so no
+    /// partial oversize data is ever spilled to disk. On the first chunk
+    /// that trips the limit, marks `inner` as finished so it is never polled
+    /// again; the caller then surfaces the terminal overflow error.
+    fn check_overflow(&mut self, additional: usize) -> bool {
+        match self.buffer_limit {
+            Some(limit) if self.buffer_size + additional > limit => {
+                self.inner_eof = true;
+                true
             }
-            Buffer::File(_, ref mut file) => {
-                file.write_all(bytes)?;
+            _ => false,
+        }
+    }
+
+    /// Persists `chunks` as one batch, spilling memory to a file the first
+    /// time the combined size crosses `threshold`. Once spilled, the batch
+    /// is handed to `AsyncFile::poll_write_vectored`, which writes it one
+    /// buffer at a time. Returns `Poll::Pending`
+    /// while the write is still in flight; call again with the same
+    /// `chunks` after being woken.
+    fn poll_write_to_buffer(&mut self, cx: &mut Context<'_>, chunks: &[Bytes]) -> Poll<std::io::Result<()>> {
+        let additional: usize = chunks.iter().map(|b| b.len()).sum();
+
+        if self.pending.is_none() {
+            match self.buffer {
+                Buffer::Memory(ref mut memory) => {
+                    if self.threshold < memory.len() + additional {
+                        let mut path = self.tmp_dir.to_path_buf();
+                        path.push(Uuid::new_v4().to_simple().to_string());
+
+                        let file = AsyncFile::open_sync(&path)?;
+                        let file = AsyncFile::from_std(file, self.file_backend)?;
+
+                        let memory_bytes = std::mem::replace(memory, BytesMut::new()).freeze();
+                        let mut bufs = Vec::with_capacity(1 + chunks.len());
+                        bufs.push(memory_bytes);
+                        bufs.extend_from_slice(chunks);
+
+                        self.buffer = Buffer::File(Arc::new(TempFile(path)), file);
+                        self.pending = Some(PendingOp::Write { bufs, offset: 0 });
+                    } else {
+                        for chunk in chunks {
+                            memory.extend_from_slice(chunk);
+                        }
+                        self.buffer_size += additional;
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Buffer::File(_, _) => {
+                    self.pending = Some(PendingOp::Write {
+                        bufs: chunks.to_vec(),
+                        offset: 0,
+                    });
+                }
             }
         }
 
-        self.buffer_size += bytes.len();
+        let file = match self.buffer {
+            Buffer::File(_, ref mut file) => file,
+            Buffer::Memory(_) => unreachable!("pending write implies a spilled buffer"),
+        };
+
+        let mut op = self.pending.take().expect("checked above");
+        let result = match op {
+            PendingOp::Write { ref bufs, ref mut offset } => poll_write_all_vectored(file, cx, bufs, offset),
+            _ => unreachable!("only a write can be pending here"),
+        };
 
-        Ok(())
+        match result {
+            Poll::Pending => {
+                self.pending = Some(op);
+                Poll::Pending
+            }
+            Poll::Ready(Ok(())) => {
+                self.buffer_size += additional;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        }
     }
 
-    fn read_from_buffer(&mut self) -> Result<Bytes, std::io::Error> {
+    /// Produces the next replay block, seeking the spill file back to start
+    /// the first time it is read. Returns `Poll::Pending` while a read is
+    /// still in flight; call again after being woken.
+    fn poll_read_from_buffer(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<Bytes>> {
         let block_size = self.produce_block_size;
         let buffer_size = self.buffer_size;
         let current_index = self.produce_index;
 
-        if buffer_size <= current_index {
-            self.produce_index = 0;
-            return Ok(Bytes::new());
-        }
+        if self.pending.is_none() {
+            if buffer_size <= current_index {
+                self.produce_index = 0;
+                return Poll::Ready(Ok(Bytes::new()));
+            }
 
-        let bytes = match self.buffer {
-            Buffer::Memory(ref memory) => {
-                let bytes = {
-                    if buffer_size <= current_index + block_size {
+            match self.buffer {
+                Buffer::Memory(ref memory) => {
+                    let bytes = if buffer_size <= current_index + block_size {
                         self.produce_index = buffer_size;
-                        let start = current_index as usize;
-                        Bytes::copy_from_slice(&memory[start..])
+                        Bytes::copy_from_slice(&memory[current_index..])
                     } else {
                         self.produce_index += block_size;
-                        let start = current_index as usize;
-                        let end = (current_index + block_size) as usize;
-                        Bytes::copy_from_slice(&memory[start..end])
-                    }
-                };
-
-                bytes
-            }
-            Buffer::File(_, ref mut file) => {
-                if current_index == 0 {
-                    file.seek(SeekFrom::Start(0))?;
-                    file.flush()?;
+                        Bytes::copy_from_slice(&memory[current_index..current_index + block_size])
+                    };
+                    return Poll::Ready(Ok(bytes));
                 }
-
-                let mut bytes = {
-                    if buffer_size <= current_index + block_size {
-                        self.produce_index = buffer_size;
-                        vec![0u8; buffer_size - current_index]
+                Buffer::File(_, _) => {
+                    if current_index == 0 {
+                        self.pending = Some(PendingOp::Rewind);
                     } else {
-                        self.produce_index += block_size;
-                        vec![0u8; block_size]
+                        let len = if buffer_size <= current_index + block_size {
+                            self.produce_index = buffer_size;
+                            buffer_size - current_index
+                        } else {
+                            self.produce_index += block_size;
+                            block_size
+                        };
+                        self.pending = Some(PendingOp::Read {
+                            buf: self.pool.checkout(len),
+                            filled: 0,
+                        });
                     }
-                };
+                }
+            }
+        }
+
+        let file = match self.buffer {
+            Buffer::File(_, ref mut file) => file,
+            Buffer::Memory(_) => unreachable!("pending read implies a spilled buffer"),
+        };
 
-                file.read_exact(bytes.as_mut_slice())?;
+        let mut op = self.pending.take().expect("checked above");
 
-                bytes.into()
+        if let PendingOp::Rewind = op {
+            if let Poll::Pending = file.poll_rewind(cx) {
+                self.pending = Some(PendingOp::Rewind);
+                return Poll::Pending;
             }
+
+            let len = if buffer_size <= current_index + block_size {
+                self.produce_index = buffer_size;
+                buffer_size - current_index
+            } else {
+                self.produce_index += block_size;
+                block_size
+            };
+            op = PendingOp::Read {
+                buf: self.pool.checkout(len),
+                filled: 0,
+            };
+        }
+
+        let result = match op {
+            PendingOp::Read { ref mut buf, ref mut filled } => poll_read_exact(file, cx, buf, filled),
+            _ => unreachable!("only a read can remain pending here"),
         };
 
-        Ok(bytes)
+        match result {
+            Poll::Pending => {
+                self.pending = Some(op);
+                Poll::Pending
+            }
+            Poll::Ready(Ok(())) => match op {
+                PendingOp::Read { buf, .. } => {
+                    let bytes = Bytes::copy_from_slice(&buf);
+                    self.pool.recycle(buf);
+                    Poll::Ready(Ok(bytes))
+                }
+                _ => unreachable!(),
+            },
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Produces the next replay block once `inner` has been fully drained
+    /// into the buffer, or `None` once the buffer itself is exhausted.
+    fn poll_replay(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, std::io::Error>>> {
+        match ready!(self.poll_read_from_buffer(cx)) {
+            Ok(bytes) if bytes.is_empty() => Poll::Ready(None),
+            Ok(bytes) => Poll::Ready(Some(Ok(bytes))),
+            Err(e) => Poll::Ready(Some(Err(e))),
+        }
+    }
+}
+
+/// Upper bound on how many chunks `poll_next` batches into one buffer
+/// write per call, so a source that always has the next chunk ready
+/// can't make one poll do unbounded work.
+const MAX_BATCH: usize = 8;
+
+/// Concatenates a batch into the single `Bytes` item handed to the caller.
+/// Consumers reassemble the body from the byte sequence regardless of chunk
+/// boundaries, so merging a batch here is transparent to them.
+fn concat_chunks(mut chunks: Vec<Bytes>) -> Bytes {
+    if chunks.len() == 1 {
+        return chunks.pop().expect("checked len == 1");
+    }
+    let mut buf = BytesMut::with_capacity(chunks.iter().map(|b| b.len()).sum());
+    for chunk in &chunks {
+        buf.extend_from_slice(chunk);
     }
+    buf.freeze()
 }
 
 impl<S, E> MessageBody for FileBufferingStream<S>
@@ -234,136 +803,366 @@ where
     ) -> Poll<Option<Result<Bytes, actix_web::Error>>> {
         let this = self.get_mut();
 
-        match this.inner_eof {
-            false => {
-                let op = ready!(Pin::new(&mut this.inner).poll_next(cx));
-                match op {
-                    Some(ref r) => {
-                        if let Ok(ref o) = r {
-                            /*if let Some(limit) = this.buffer_limit {
-                                if this.buffer_size + o.len() > limit {
-                                    return Poll::Ready(Some(Err(actix_web::Error::from(status::))));
-                                }
-                            }*/
-
-                            this.write_to_buffer(o)?;
+        if this.overflowed {
+            return Poll::Ready(None);
+        }
+
+        if this.pending_overflow {
+            this.pending_overflow = false;
+            this.overflowed = true;
+            return Poll::Ready(Some(Err(actix_web::error::ErrorPayloadTooLarge(
+                "request body exceeds buffer_limit",
+            ))));
+        }
+
+        if !this.inner_eof || !this.pending_chunks.is_empty() {
+            let mut chunks = std::mem::take(&mut this.pending_chunks);
+
+            if chunks.is_empty() {
+                while chunks.len() < MAX_BATCH {
+                    match Pin::new(&mut this.inner).poll_next(cx) {
+                        Poll::Ready(Some(Ok(bytes))) => {
+                            let queued: usize = chunks.iter().map(|b| b.len()).sum();
+                            if this.check_overflow(queued + bytes.len()) {
+                                this.pending_overflow = true;
+                                break;
+                            }
+                            chunks.push(bytes);
+                        }
+                        Poll::Ready(Some(Err(e))) => {
+                            if chunks.is_empty() {
+                                return Poll::Ready(Some(Err(e.into())));
+                            }
+                            // Already have valid chunks to emit; surfacing this
+                            // error would have to wait for the next poll, and
+                            // `inner` can't be polled again after erroring, so
+                            // it's dropped in favor of emitting what we have.
+                            this.inner_eof = true;
+                            break;
+                        }
+                        Poll::Ready(None) => {
+                            this.inner_eof = true;
+                            break;
+                        }
+                        Poll::Pending => {
+                            if chunks.is_empty() {
+                                return Poll::Pending;
+                            }
+                            break;
                         }
                     }
-                    None => {
-                        this.inner_eof = true;
-                    }
-                };
+                }
+            }
 
-                Poll::Ready(op.map(|res| res.map_err(Into::into)))
+            if chunks.is_empty() {
+                if this.pending_overflow {
+                    this.pending_overflow = false;
+                    this.overflowed = true;
+                    return Poll::Ready(Some(Err(actix_web::error::ErrorPayloadTooLarge(
+                        "request body exceeds buffer_limit",
+                    ))));
+                }
+                // `inner` just reached its real end this pass; report it
+                // immediately rather than dropping into a replay of what was
+                // already streamed out above. A replay only ever begins in
+                // response to an explicit `rewind()`, handled below.
+                return Poll::Ready(None);
             }
-            true => {
-                let bytes = this.read_from_buffer()?;
-                if bytes.len() == 0 {
-                    Poll::Ready(None)
-                } else {
-                    Poll::Ready(Some(Ok(bytes)))
+
+            return match this.poll_write_to_buffer(cx, &chunks) {
+                Poll::Pending => {
+                    this.pending_chunks = chunks;
+                    Poll::Pending
                 }
+                Poll::Ready(Ok(())) => Poll::Ready(Some(Ok(concat_chunks(chunks)))),
+                Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e.into()))),
+            };
+        }
+
+        if !this.replaying {
+            return Poll::Ready(None);
+        }
+
+        match ready!(this.poll_replay(cx)) {
+            Some(Ok(b)) => Poll::Ready(Some(Ok(b))),
+            Some(Err(e)) => Poll::Ready(Some(Err(e.into()))),
+            None => {
+                this.replaying = false;
+                Poll::Ready(None)
             }
         }
-        /*let mut stream = self.project().stream;
-        loop {
-            let stream = stream.as_mut();
-            return Poll::Ready(match ready!(stream.poll_next(cx)) {
-                Some(Ok(ref bytes)) if bytes.is_empty() => continue,
-                opt => opt.map(|res| res.map_err(Into::into)),
-            });
-        }*/
     }
 }
-/*
-impl<S> MessageBody for FileBufferingStream<S> 
+
+impl<S> Stream for FileBufferingStream<S>
 where
-    S: MessageBody + Unpin,
+    S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
 {
-    fn poll_next(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-    ) -> Poll<Option<Result<Bytes, actix_web::Error>>> {
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
 
-        match this.inner_eof {
-            false => {
-                let op = ready!(Pin::new(&mut this.inner).poll_next(cx));
-                match op {
-                    Some(ref r) => {
-                        if let Ok(ref o) = r {
-                            if let Some(limit) = this.buffer_limit {
-                                if this.buffer_size + o.len() > limit {
-                                    return Poll::Ready(Some(Err(actix_web::Error::from(status::))));
-                                }
-                            }
+        if this.overflowed {
+            return Poll::Ready(None);
+        }
 
-                            this.write_to_buffer(o)?;
+        if this.pending_overflow {
+            this.pending_overflow = false;
+            this.overflowed = true;
+            return Poll::Ready(Some(Err(PayloadError::Overflow)));
+        }
+
+        if !this.inner_eof || !this.pending_chunks.is_empty() {
+            let mut chunks = std::mem::take(&mut this.pending_chunks);
+
+            if chunks.is_empty() {
+                while chunks.len() < MAX_BATCH {
+                    match Pin::new(&mut this.inner).poll_next(cx) {
+                        Poll::Ready(Some(Ok(bytes))) => {
+                            let queued: usize = chunks.iter().map(|b| b.len()).sum();
+                            if this.check_overflow(queued + bytes.len()) {
+                                this.pending_overflow = true;
+                                break;
+                            }
+                            chunks.push(bytes);
+                        }
+                        Poll::Ready(Some(Err(e))) => {
+                            if chunks.is_empty() {
+                                return Poll::Ready(Some(Err(e)));
+                            }
+                            // Already have valid chunks to emit; surfacing this
+                            // error would have to wait for the next poll, and
+                            // `inner` can't be polled again after erroring, so
+                            // it's dropped in favor of emitting what we have.
+                            this.inner_eof = true;
+                            break;
+                        }
+                        Poll::Ready(None) => {
+                            this.inner_eof = true;
+                            break;
+                        }
+                        Poll::Pending => {
+                            if chunks.is_empty() {
+                                return Poll::Pending;
+                            }
+                            break;
                         }
                     }
-                    None => {
-                        this.inner_eof = true;
-                    }
-                };
+                }
+            }
 
-                Poll::Ready(op)
+            if chunks.is_empty() {
+                if this.pending_overflow {
+                    this.pending_overflow = false;
+                    this.overflowed = true;
+                    return Poll::Ready(Some(Err(PayloadError::Overflow)));
+                }
+                // `inner` just reached its real end this pass; report it
+                // immediately rather than dropping into a replay of what was
+                // already streamed out above. A replay only ever begins in
+                // response to an explicit `rewind()`, handled below.
+                return Poll::Ready(None);
             }
-            true => {
-                let bytes = this.read_from_buffer()?;
-                if bytes.len() == 0 {
-                    Poll::Ready(None)
-                } else {
-                    Poll::Ready(Some(Ok(bytes)))
+
+            return match this.poll_write_to_buffer(cx, &chunks) {
+                Poll::Pending => {
+                    this.pending_chunks = chunks;
+                    Poll::Pending
                 }
+                Poll::Ready(Ok(())) => Poll::Ready(Some(Ok(concat_chunks(chunks)))),
+                Poll::Ready(Err(e)) => Poll::Ready(Some(Err(PayloadError::Io(e)))),
+            };
+        }
+
+        if !this.replaying {
+            return Poll::Ready(None);
+        }
+
+        match ready!(this.poll_replay(cx)) {
+            Some(Ok(b)) => Poll::Ready(Some(Ok(b))),
+            Some(Err(e)) => Poll::Ready(Some(Err(PayloadError::Io(e)))),
+            None => {
+                this.replaying = false;
+                Poll::Ready(None)
             }
         }
     }
+}
+
+/// Immutable view of a fully-buffered payload, cheap to clone: `Bytes`
+/// already shares its backing allocation via refcounting, and the spill
+/// file is kept alive by `TempFile`'s `Arc` rather than copied.
+#[derive(Clone)]
+enum BufferedData {
+    Memory(Bytes),
+    File(Arc<TempFile>),
+}
 
-    fn size(&self) -> actix_web::dev::BodySize {
-        todo!()
+/// An independent, cloneable replay handle produced by
+/// [`FileBufferingStream::into_reader`]. Each clone has its own produce
+/// cursor (and, for a spilled buffer, its own open file handle), so several
+/// readers can consume the same buffered payload concurrently without
+/// interfering with one another.
+pub struct BufferedBodyReader {
+    data: BufferedData,
+    buffer_size: usize,
+    produce_block_size: usize,
+    file_backend: FileBackend,
+    pool: BytePool,
+    produce_index: usize,
+    file: Option<AsyncFile>,
+    pending: Option<PendingOp>,
+}
+
+impl Clone for BufferedBodyReader {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            buffer_size: self.buffer_size,
+            produce_block_size: self.produce_block_size,
+            file_backend: self.file_backend,
+            pool: self.pool.clone(),
+            produce_index: 0,
+            file: None,
+            pending: None,
+        }
     }
 }
-*/
 
-impl<S> Stream for FileBufferingStream<S>
-where
-    S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
-{
-    type Item = Result<Bytes, PayloadError>;
+impl BufferedBodyReader {
+    /// Restarts this reader from the beginning. Sibling clones are
+    /// unaffected, since each tracks its own cursor.
+    pub fn rewind(&mut self) {
+        self.produce_index = 0;
+        self.pending = None;
+        self.file = None;
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
+    /// Produces the next replay block, opening the spill file (if any) and
+    /// seeking it back to start the first time this reader is polled.
+    /// Returns `Poll::Pending` while a read is still in flight; call again
+    /// after being woken.
+    fn poll_next_block(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<Bytes>> {
+        let block_size = self.produce_block_size;
+        let buffer_size = self.buffer_size;
+        let current_index = self.produce_index;
 
-        match this.inner_eof {
-            false => {
-                let op = ready!(Pin::new(&mut this.inner).poll_next(cx));
-                match op {
-                    Some(ref r) => {
-                        if let Ok(ref o) = r {
-                            if let Some(limit) = this.buffer_limit {
-                                if this.buffer_size + o.len() > limit {
-                                    return Poll::Ready(Some(Err(PayloadError::Overflow)));
-                                }
-                            }
+        if self.pending.is_none() {
+            if buffer_size <= current_index {
+                self.produce_index = 0;
+                return Poll::Ready(Ok(Bytes::new()));
+            }
 
-                            this.write_to_buffer(o)?;
-                        }
+            match &self.data {
+                BufferedData::Memory(memory) => {
+                    let bytes = if buffer_size <= current_index + block_size {
+                        self.produce_index = buffer_size;
+                        memory.slice(current_index..)
+                    } else {
+                        self.produce_index += block_size;
+                        memory.slice(current_index..current_index + block_size)
+                    };
+                    return Poll::Ready(Ok(bytes));
+                }
+                BufferedData::File(temp_file) => {
+                    if self.file.is_none() {
+                        let file = AsyncFile::open_read(&temp_file.0)?;
+                        self.file = Some(AsyncFile::from_std(file, self.file_backend)?);
                     }
-                    None => {
-                        this.inner_eof = true;
+
+                    if current_index == 0 {
+                        self.pending = Some(PendingOp::Rewind);
+                    } else {
+                        let len = if buffer_size <= current_index + block_size {
+                            self.produce_index = buffer_size;
+                            buffer_size - current_index
+                        } else {
+                            self.produce_index += block_size;
+                            block_size
+                        };
+                        self.pending = Some(PendingOp::Read {
+                            buf: self.pool.checkout(len),
+                            filled: 0,
+                        });
                     }
-                };
+                }
+            }
+        }
 
-                Poll::Ready(op)
+        let file = self.file.as_mut().expect("pending op implies an open file");
+
+        let mut op = self.pending.take().expect("checked above");
+
+        if let PendingOp::Rewind = op {
+            if let Poll::Pending = file.poll_rewind(cx) {
+                self.pending = Some(PendingOp::Rewind);
+                return Poll::Pending;
             }
-            true => {
-                let bytes = this.read_from_buffer()?;
-                if bytes.len() == 0 {
-                    Poll::Ready(None)
-                } else {
-                    Poll::Ready(Some(Ok(bytes)))
-                }
+
+            let len = if buffer_size <= current_index + block_size {
+                self.produce_index = buffer_size;
+                buffer_size - current_index
+            } else {
+                self.produce_index += block_size;
+                block_size
+            };
+            op = PendingOp::Read {
+                buf: self.pool.checkout(len),
+                filled: 0,
+            };
+        }
+
+        let result = match op {
+            PendingOp::Read { ref mut buf, ref mut filled } => poll_read_exact(file, cx, buf, filled),
+            _ => unreachable!("only a read can remain pending here"),
+        };
+
+        match result {
+            Poll::Pending => {
+                self.pending = Some(op);
+                Poll::Pending
             }
+            Poll::Ready(Ok(())) => match op {
+                PendingOp::Read { buf, .. } => {
+                    let bytes = Bytes::copy_from_slice(&buf);
+                    self.pool.recycle(buf);
+                    Poll::Ready(Ok(bytes))
+                }
+                _ => unreachable!(),
+            },
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl MessageBody for BufferedBodyReader {
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.buffer_size as u64)
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, actix_web::Error>>> {
+        let this = self.get_mut();
+        match ready!(this.poll_next_block(cx)) {
+            Ok(bytes) if bytes.is_empty() => Poll::Ready(None),
+            Ok(bytes) => Poll::Ready(Some(Ok(bytes))),
+            Err(e) => Poll::Ready(Some(Err(e.into()))),
+        }
+    }
+}
+
+impl Stream for BufferedBodyReader {
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match ready!(this.poll_next_block(cx)) {
+            Ok(bytes) if bytes.is_empty() => Poll::Ready(None),
+            Ok(bytes) => Poll::Ready(Some(Ok(bytes))),
+            Err(e) => Poll::Ready(Some(Err(PayloadError::Io(e)))),
         }
     }
 }
@@ -0,0 +1,355 @@
+use std::io::Write;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::{cell::RefCell, sync::Arc};
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::ServiceRequest,
+    dev::{Body, Payload, ResponseBody, ServiceResponse},
+    error::ErrorInternalServerError,
+    http::{HeaderName, HeaderValue},
+    Error, HttpMessage,
+};
+use futures::future::{ok, Future, Ready};
+use futures::{FutureExt, StreamExt};
+use openssl::hash::{Hasher, MessageDigest};
+
+use crate::buffering::{enable_response_buffering, next_body_chunk, FileBufferingStreamBuilder};
+use crate::verify::VerifyErrorType;
+
+/// Hash algorithm used to compute the body digest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn message_digest(&self) -> MessageDigest {
+        match self {
+            DigestAlgorithm::Sha256 => MessageDigest::sha256(),
+            DigestAlgorithm::Sha512 => MessageDigest::sha512(),
+        }
+    }
+
+    /// Name as used in the legacy `Digest` header (e.g. `SHA-256`).
+    fn legacy_name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "SHA-256",
+            DigestAlgorithm::Sha512 => "SHA-512",
+        }
+    }
+
+    /// Name as used in the structured `Content-Digest` header (e.g. `sha-256`).
+    fn structured_name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha-256",
+            DigestAlgorithm::Sha512 => "sha-512",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha-256" => Some(DigestAlgorithm::Sha256),
+            "sha-512" => Some(DigestAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// Which header form to emit/expect: the legacy `Digest` header or the
+/// structured `Content-Digest` header (`sha-256=:<base64>:`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DigestHeaderStyle {
+    Legacy,
+    Structured,
+}
+
+fn format_header(algorithm: DigestAlgorithm, style: DigestHeaderStyle, hash: &[u8]) -> (HeaderName, String) {
+    let encoded = base64::encode(hash);
+    match style {
+        DigestHeaderStyle::Legacy => (
+            HeaderName::from_static("digest"),
+            format!("{}={}", algorithm.legacy_name(), encoded),
+        ),
+        DigestHeaderStyle::Structured => (
+            HeaderName::from_static("content-digest"),
+            format!("{}=:{}:", algorithm.structured_name(), encoded),
+        ),
+    }
+}
+
+/// Parses either a `Digest: SHA-256=<base64>` or a
+/// `Content-Digest: sha-256=:<base64>:` header value.
+fn parse_header(value: &str) -> Option<(DigestAlgorithm, Vec<u8>)> {
+    let mut parts = value.splitn(2, '=');
+    let name = parts.next()?;
+    let rest = parts.next()?;
+
+    let algorithm = DigestAlgorithm::from_name(name.trim())?;
+    let encoded = rest.trim();
+    let encoded = encoded
+        .strip_prefix(':')
+        .and_then(|v| v.strip_suffix(':'))
+        .unwrap_or(encoded);
+
+    let bytes = base64::decode(encoded).ok()?;
+    Some((algorithm, bytes))
+}
+
+/// Middleware that computes a digest over the response body and emits it as a
+/// `Digest`/`Content-Digest` header, mirroring `DetachedJwsSign`.
+pub struct DigestSign {
+    algorithm: DigestAlgorithm,
+    style: DigestHeaderStyle,
+    buffering: Arc<FileBufferingStreamBuilder>,
+}
+
+impl DigestSign {
+    pub fn new() -> Self {
+        Self {
+            algorithm: DigestAlgorithm::Sha256,
+            style: DigestHeaderStyle::Legacy,
+            buffering: Arc::new(FileBufferingStreamBuilder::new()),
+        }
+    }
+
+    pub fn algorithm(mut self, v: DigestAlgorithm) -> Self {
+        self.algorithm = v;
+        self
+    }
+
+    pub fn header_style(mut self, v: DigestHeaderStyle) -> Self {
+        self.style = v;
+        self
+    }
+
+    pub fn override_buffering(mut self, v: Arc<FileBufferingStreamBuilder>) -> Self {
+        self.buffering = v;
+        self
+    }
+}
+
+impl<S> Transform<S> for DigestSign
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SignMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(SignMiddleware {
+            service,
+            algorithm: self.algorithm,
+            style: self.style,
+            buffering: Arc::clone(&self.buffering),
+        })
+    }
+}
+
+pub struct SignMiddleware<S> {
+    service: S,
+    algorithm: DigestAlgorithm,
+    style: DigestHeaderStyle,
+    buffering: Arc<FileBufferingStreamBuilder>,
+}
+
+impl<S> Service for SignMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+        let algorithm = self.algorithm;
+        let style = self.style;
+        let buffering = self.buffering.clone();
+
+        async move {
+            let svc_res = fut.await?;
+
+            let mut svc_res = enable_response_buffering(&buffering, svc_res);
+
+            let mut hasher =
+                Hasher::new(algorithm.message_digest()).map_err(ErrorInternalServerError)?;
+
+            let mut stream = match svc_res.take_body() {
+                ResponseBody::Body(b) => b,
+                ResponseBody::Other(_) => unreachable!("enable_response_buffering always sets ResponseBody::Body"),
+            };
+            while let Some(chunk) = next_body_chunk(&mut stream).await {
+                hasher.write_all(&chunk?)?;
+            }
+
+            let hash = hasher.finish().map_err(ErrorInternalServerError)?;
+
+            // The body was just drained to compute the digest; rewind it so
+            // the same buffered bytes are what actually gets sent back to
+            // the client.
+            stream.rewind();
+            let mut svc_res =
+                svc_res.map_body(|_, _| ResponseBody::Body(Body::from_message(stream)));
+            let (name, value) = format_header(algorithm, style, &hash);
+            svc_res
+                .headers_mut()
+                .insert(name, HeaderValue::from_str(&value).map_err(ErrorInternalServerError)?);
+
+            Ok(svc_res)
+        }
+        .boxed_local()
+    }
+}
+
+pub trait DigestVerifyConfig<'a> {
+    type ErrorHandler: Future<Output = Error>;
+
+    fn error_handler(
+        &'a self,
+        req: &'a mut ServiceRequest,
+        error: VerifyErrorType,
+    ) -> Self::ErrorHandler;
+}
+
+/// Middleware that recomputes the body digest and rejects requests whose
+/// `Digest`/`Content-Digest` header does not match, mirroring
+/// `DetachedJwsVerify`.
+pub struct DigestVerify<T> {
+    config: Arc<T>,
+    buffering: Arc<FileBufferingStreamBuilder>,
+}
+
+impl<T> DigestVerify<T> {
+    pub fn new(config: Arc<T>) -> Self {
+        Self {
+            config,
+            buffering: Arc::new(FileBufferingStreamBuilder::new()),
+        }
+    }
+
+    pub fn override_buffering(mut self, v: Arc<FileBufferingStreamBuilder>) -> Self {
+        self.buffering = v;
+        self
+    }
+}
+
+impl<S, T> Transform<S> for DigestVerify<T>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    T: for<'a> DigestVerifyConfig<'a> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = VerifyMiddleware<S, T>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(VerifyMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            config: Arc::clone(&self.config),
+            buffering: Arc::clone(&self.buffering),
+        })
+    }
+}
+
+pub struct VerifyMiddleware<S, T> {
+    service: Rc<RefCell<S>>,
+    config: Arc<T>,
+    buffering: Arc<FileBufferingStreamBuilder>,
+}
+
+impl<S, T> Service for VerifyMiddleware<S, T>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    T: for<'a> DigestVerifyConfig<'a> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        let mut svc = self.service.clone();
+        let config = self.config.clone();
+        let buffering = self.buffering.clone();
+
+        async move {
+            let header = req
+                .headers()
+                .get("content-digest")
+                .or_else(|| req.headers().get("digest"));
+
+            let (algorithm, expected) = match header.and_then(|h| h.to_str().ok()).and_then(parse_header) {
+                Some(o) => o,
+                None => {
+                    return Err(config
+                        .error_handler(&mut req, VerifyErrorType::HeaderNotFound)
+                        .await)
+                }
+            };
+
+            let mut hasher = match Hasher::new(algorithm.message_digest()) {
+                Ok(o) => o,
+                Err(e) => {
+                    return Err(config
+                        .error_handler(&mut req, VerifyErrorType::Other(e.into()))
+                        .await)
+                }
+            };
+
+            // An empty stream still drives the hasher to a finish() over zero
+            // bytes, so empty bodies are checked rather than skipped.
+            //
+            // Built directly off the raw payload (there's no should_verify-
+            // style consumer that needs to see the body before we do), so
+            // there's only one buffering pass for the whole request.
+            let mut stream = buffering.build(req.take_payload());
+            while let Some(chunk) = next_body_chunk(&mut stream).await {
+                hasher.write_all(&chunk?)?;
+            }
+
+            let actual = match hasher.finish() {
+                Ok(o) => o,
+                Err(e) => {
+                    return Err(config
+                        .error_handler(&mut req, VerifyErrorType::Other(e.into()))
+                        .await)
+                }
+            };
+
+            // `memcmp::eq` panics on inputs of different lengths, so a
+            // wrong-length digest is rejected before it gets there.
+            if actual.len() != expected.len() || !openssl::memcmp::eq(&actual, &expected) {
+                return Err(config
+                    .error_handler(&mut req, VerifyErrorType::DigestMismatch)
+                    .await);
+            }
+
+            if let Some(reader) = stream.into_reader() {
+                req.set_payload(Payload::Stream(reader.boxed_local()));
+            }
+
+            svc.call(req).await
+        }
+        .boxed_local()
+    }
+}
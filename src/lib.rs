@@ -116,8 +116,16 @@
 //! }
 //! 
 //! ```
+pub mod buffering;
+pub mod digest;
+pub mod message_signature;
 pub mod sign;
 pub mod verify;
 
+pub use crate::buffering::FileBufferingStreamBuilder;
+pub use crate::digest::{DigestAlgorithm, DigestHeaderStyle, DigestSign, DigestVerify, DigestVerifyConfig};
+pub use crate::message_signature::{
+    MessageSignatureSign, MessageSignatureSignConfig, MessageSignatureVerify, MessageSignatureVerifyConfig,
+};
 pub use crate::sign::{DetachedJwsSign, DetachedJwsSignConfig};
-pub use crate::verify::{DetachedJwsVerify, DetachedJwsVerifyConfig, VerifyErrorType};
+pub use crate::verify::{DetachedJwsVerify, DetachedJwsVerifyConfig, VerifiedJws, VerifyErrorType};
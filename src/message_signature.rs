@@ -0,0 +1,387 @@
+//! HTTP Message Signatures ([Cavage draft](https://datatracker.ietf.org/doc/html/draft-cavage-http-signatures)
+//! / [RFC 9421](https://datatracker.ietf.org/doc/html/rfc9421)) as an
+//! alternative to detached JWS. Unlike detached JWS, the signature covers a
+//! selected set of request components (headers and the request target)
+//! instead of only the body, so it can protect things like `Host` and `Date`
+//! that a body-only scheme can't see.
+use std::io::Write;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::{cell::RefCell, sync::Arc};
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::ServiceRequest,
+    dev::{Body, ServiceResponse},
+    error::ErrorInternalServerError,
+    http::{HeaderMap, HeaderName, HeaderValue},
+    Error,
+};
+use detached_jws::{Sign, Verify};
+use futures::future::{ok, Future, Ready};
+use futures::FutureExt;
+
+use crate::verify::VerifyErrorType;
+
+/// Algorithms known to be safe to accept. Anything else (including
+/// deprecated signature algorithms such as `rsa-sha1`/`hmac-sha1`) is
+/// rejected explicitly rather than silently attempted.
+const SUPPORTED_ALGORITHMS: &[&str] = &["rsa-sha256", "rsa-pss-sha256", "hmac-sha256", "ecdsa-p256-sha256"];
+
+/// Pseudo-header naming the synthesized `<method> <path+query>` component.
+const REQUEST_TARGET: &str = "(request-target)";
+
+struct SignatureParams {
+    key_id: Option<String>,
+    algorithm: String,
+    covered_headers: Vec<String>,
+    signature: Vec<u8>,
+}
+
+/// Parses the `Signature`/`Signature-Input` header pair, falling back to the
+/// legacy `Authorization: Signature ...` scheme.
+fn parse_params(headers: &HeaderMap) -> Option<SignatureParams> {
+    if let (Some(input), Some(signature)) = (headers.get("signature-input"), headers.get("signature")) {
+        return parse_structured(input.to_str().ok()?, signature.to_str().ok()?);
+    }
+
+    if let Some(auth) = headers.get("authorization") {
+        let value = auth.to_str().ok()?;
+        let rest = value.strip_prefix("Signature ")?;
+        return parse_params_list(rest, ',');
+    }
+
+    None
+}
+
+fn parse_structured(input: &str, signature: &str) -> Option<SignatureParams> {
+    // sig1=("(request-target)" "host" "date");keyid="k1";alg="rsa-sha256"
+    let (_, rest) = input.split_once('=')?;
+    let (components, params) = rest.split_once(';')?;
+
+    let covered_headers = components
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split_whitespace()
+        .map(|s| s.trim_matches('"').to_owned())
+        .collect();
+
+    let mut parsed = parse_params_list(params, ';')?;
+    parsed.covered_headers = covered_headers;
+
+    // sig1=:<base64>:
+    let (_, sig) = signature.split_once('=')?;
+    let sig = sig.trim().trim_start_matches(':').trim_end_matches(':');
+    parsed.signature = base64::decode(sig).ok()?;
+
+    Some(parsed)
+}
+
+/// Parses a `key="value"` list separated by `sep` — `,` for the legacy
+/// `Authorization: Signature keyId="...",algorithm="...",headers="...",signature="..."`
+/// form, `;` for the `keyid="k1";alg="rsa-sha256"` params following the
+/// structured `Signature-Input` component list.
+fn parse_params_list(list: &str, sep: char) -> Option<SignatureParams> {
+    let mut key_id = None;
+    let mut algorithm = None;
+    let mut covered_headers = Vec::new();
+    let mut signature = Vec::new();
+
+    for part in list.split(sep) {
+        let (k, v) = part.trim().split_once('=')?;
+        let v = v.trim().trim_matches('"');
+
+        match k.trim() {
+            "keyId" | "keyid" => key_id = Some(v.to_owned()),
+            "algorithm" | "alg" => algorithm = Some(v.to_owned()),
+            "headers" => covered_headers = v.split_whitespace().map(str::to_owned).collect(),
+            "signature" => signature = base64::decode(v).ok()?,
+            _ => {}
+        }
+    }
+
+    Some(SignatureParams {
+        key_id,
+        algorithm: algorithm?,
+        covered_headers,
+        signature,
+    })
+}
+
+/// Reconstructs the signing string: each covered component joined by `\n`,
+/// in the order listed.
+fn build_signing_string(method: &str, path_and_query: &str, headers: &HeaderMap, covered: &[String]) -> Option<String> {
+    let mut lines = Vec::with_capacity(covered.len());
+
+    for name in covered {
+        if name.eq_ignore_ascii_case(REQUEST_TARGET) {
+            lines.push(format!("{}: {} {}", REQUEST_TARGET, method.to_lowercase(), path_and_query));
+        } else {
+            let value = headers.get(name.as_str())?.to_str().ok()?;
+            lines.push(format!("{}: {}", name.to_lowercase(), value));
+        }
+    }
+
+    Some(lines.join("\n"))
+}
+
+pub trait MessageSignatureVerifyConfig<'a> {
+    type Verifier: detached_jws::Verify;
+    type ErrorHandler: Future<Output = Error>;
+
+    fn get_verifier(&'a self, key_id: Option<&str>, algorithm: &str) -> Option<Self::Verifier>;
+
+    fn error_handler(
+        &'a self,
+        req: &'a mut ServiceRequest,
+        error: VerifyErrorType,
+    ) -> Self::ErrorHandler;
+}
+
+pub struct MessageSignatureVerify<T> {
+    config: Arc<T>,
+}
+
+impl<T> MessageSignatureVerify<T> {
+    pub fn new(config: Arc<T>) -> Self {
+        Self { config }
+    }
+}
+
+impl<S, T> Transform<S> for MessageSignatureVerify<T>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    T: for<'a> MessageSignatureVerifyConfig<'a> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = Middleware<S, T>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(Middleware {
+            service: Rc::new(RefCell::new(service)),
+            config: Arc::clone(&self.config),
+        })
+    }
+}
+
+pub struct Middleware<S, T> {
+    service: Rc<RefCell<S>>,
+    config: Arc<T>,
+}
+
+impl<S, T> Service for Middleware<S, T>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    T: for<'a> MessageSignatureVerifyConfig<'a> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        let mut svc = self.service.clone();
+        let config = self.config.clone();
+
+        async move {
+            let params = match parse_params(req.headers()) {
+                Some(o) => o,
+                None => {
+                    return Err(config
+                        .error_handler(&mut req, VerifyErrorType::HeaderNotFound)
+                        .await)
+                }
+            };
+
+            if !SUPPORTED_ALGORITHMS.contains(&params.algorithm.as_str()) {
+                return Err(config
+                    .error_handler(&mut req, VerifyErrorType::UnsupportedAlgorithm(params.algorithm))
+                    .await);
+            }
+
+            let signing_string = match build_signing_string(
+                req.method().as_str(),
+                req.uri()
+                    .path_and_query()
+                    .map(|p| p.as_str())
+                    .unwrap_or_else(|| req.uri().path()),
+                req.headers(),
+                &params.covered_headers,
+            ) {
+                Some(o) => o,
+                None => {
+                    return Err(config
+                        .error_handler(
+                            &mut req,
+                            VerifyErrorType::Other(anyhow::anyhow!("covered header missing from request")),
+                        )
+                        .await)
+                }
+            };
+
+            let mut verifier = match config.get_verifier(params.key_id.as_deref(), &params.algorithm) {
+                Some(o) => o,
+                None => {
+                    return Err(config
+                        .error_handler(
+                            &mut req,
+                            VerifyErrorType::Other(anyhow::anyhow!("no verifier for the given key id/algorithm")),
+                        )
+                        .await)
+                }
+            };
+
+            if let Err(e) = verifier.write_all(signing_string.as_bytes()) {
+                return Err(config
+                    .error_handler(&mut req, VerifyErrorType::Other(e.into()))
+                    .await);
+            }
+
+            match verifier.verify(&params.signature) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(config
+                        .error_handler(&mut req, VerifyErrorType::IncorrectSignature)
+                        .await)
+                }
+                Err(e) => {
+                    return Err(config
+                        .error_handler(&mut req, VerifyErrorType::Other(e.into()))
+                        .await)
+                }
+            }
+
+            svc.call(req).await
+        }
+        .boxed_local()
+    }
+}
+
+pub trait MessageSignatureSignConfig<'a> {
+    type Signer: Sign;
+
+    /// Returns the signer, the algorithm name to advertise, the key id (if
+    /// any), and the ordered list of components to cover.
+    fn get_signer(&'a self) -> (Self::Signer, String, Option<String>, Vec<String>);
+}
+
+pub struct MessageSignatureSign<T> {
+    config: Arc<T>,
+}
+
+impl<T> MessageSignatureSign<T> {
+    pub fn new(config: Arc<T>) -> Self {
+        Self { config }
+    }
+}
+
+impl<S, T> Transform<S> for MessageSignatureSign<T>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    T: for<'a> MessageSignatureSignConfig<'a> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SignMiddleware<S, T>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(SignMiddleware {
+            service,
+            config: Arc::clone(&self.config),
+        })
+    }
+}
+
+pub struct SignMiddleware<S, T> {
+    service: S,
+    config: Arc<T>,
+}
+
+impl<S, T> Service for SignMiddleware<S, T>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    T: for<'a> MessageSignatureSignConfig<'a> + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+        let config = self.config.clone();
+
+        async move {
+            let svc_res = fut.await?;
+
+            let (mut signer, algorithm, key_id, covered_headers) = config.get_signer();
+
+            let method = svc_res.request().method().as_str();
+            let path_and_query = svc_res
+                .request()
+                .uri()
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or_else(|| svc_res.request().uri().path())
+                .to_owned();
+
+            // Response headers take precedence so a signature can cover a
+            // header (e.g. `Digest`) only set on the way out.
+            let mut headers = svc_res.request().headers().clone();
+            for (name, value) in svc_res.headers() {
+                headers.insert(name.clone(), value.clone());
+            }
+
+            let signing_string = build_signing_string(method, &path_and_query, &headers, &covered_headers)
+                .ok_or_else(|| ErrorInternalServerError("covered header missing from response"))?;
+
+            signer
+                .write_all(signing_string.as_bytes())
+                .map_err(ErrorInternalServerError)?;
+            let signature = signer.get_sign().map_err(ErrorInternalServerError)?;
+
+            let mut svc_res = svc_res;
+            let components = covered_headers
+                .iter()
+                .map(|h| format!("\"{}\"", h))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let mut input = format!("sig1=({})", components);
+            if let Some(ref kid) = key_id {
+                input.push_str(&format!(";keyid=\"{}\"", kid));
+            }
+            input.push_str(&format!(";alg=\"{}\"", algorithm));
+
+            svc_res.headers_mut().insert(
+                HeaderName::from_static("signature-input"),
+                HeaderValue::from_str(&input).map_err(ErrorInternalServerError)?,
+            );
+            svc_res.headers_mut().insert(
+                HeaderName::from_static("signature"),
+                HeaderValue::from_str(&format!("sig1=:{}:", base64::encode(&signature)))
+                    .map_err(ErrorInternalServerError)?,
+            );
+
+            Ok(svc_res)
+        }
+        .boxed_local()
+    }
+}
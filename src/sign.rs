@@ -8,15 +8,15 @@ use std::{
 use actix_service::{Service, Transform};
 use actix_web::{
     dev::ServiceRequest,
-    dev::{Body, ServiceResponse},
+    dev::{Body, ResponseBody, ServiceResponse},
     error::ErrorInternalServerError,
     http::{HeaderName, HeaderValue},
     Error,
 };
-use actix_web_buffering::{enable_response_buffering, FileBufferingStreamWrapper};
+use crate::buffering::{enable_response_buffering, next_body_chunk, FileBufferingStreamBuilder};
 use detached_jws::{JwsHeader, SerializeJwsWriter, Sign};
 use futures::future::{ok, Future, Ready};
-use futures::{stream::StreamExt, FutureExt};
+use futures::FutureExt;
 
 pub trait DetachedJwsSignConfig<'a> {
     type Signer: Sign;
@@ -26,21 +26,43 @@ pub trait DetachedJwsSignConfig<'a> {
 
 pub struct DetachedJwsSign<T> {
     config: Arc<T>,
-    buffering: Arc<FileBufferingStreamWrapper>,
+    buffering: Arc<FileBufferingStreamBuilder>,
 }
 
 impl<T> DetachedJwsSign<T> {
     pub fn new(config: Arc<T>) -> Self {
         Self {
             config,
-            buffering: Arc::new(FileBufferingStreamWrapper::new()),
+            buffering: Arc::new(FileBufferingStreamBuilder::new()),
         }
     }
 
-    pub fn override_buffering(mut self, v: Arc<FileBufferingStreamWrapper>) -> Self {
+    pub fn override_buffering(mut self, v: Arc<FileBufferingStreamBuilder>) -> Self {
         self.buffering = v;
         self
     }
+
+    /// In-memory size (in bytes) the response body may reach before it
+    /// spills to a temp file.
+    pub fn buffering_threshold(self, v: usize) -> Self {
+        self.with_buffering(|b| b.threshold(v))
+    }
+
+    /// Directory used for spilled response bodies.
+    pub fn buffering_tmp_dir(self, v: impl AsRef<std::path::Path>) -> Self {
+        self.with_buffering(|b| b.tmp_dir(v))
+    }
+
+    /// Largest response body accepted; larger bodies are rejected before
+    /// being buffered.
+    pub fn max_body_size(self, v: usize) -> Self {
+        self.with_buffering(|b| b.buffer_limit(Some(v)))
+    }
+
+    fn with_buffering(mut self, f: impl FnOnce(FileBufferingStreamBuilder) -> FileBufferingStreamBuilder) -> Self {
+        self.buffering = Arc::new(f((*self.buffering).clone()));
+        self
+    }
 }
 
 impl<S, T> Transform<S> for DetachedJwsSign<T>
@@ -67,7 +89,7 @@ where
 pub struct Middleware<S, T> {
     service: S,
     config: Arc<T>,
-    buffering: Arc<FileBufferingStreamWrapper>,
+    buffering: Arc<FileBufferingStreamBuilder>,
 }
 
 impl<S, T> Service for Middleware<S, T>
@@ -99,14 +121,22 @@ where
             let mut writer = SerializeJwsWriter::new(Vec::new(), algorithm, jws_header, signer)
                 .map_err(|e| ErrorInternalServerError(e))?;
 
-            let mut stream = svc_res.take_body();
-            while let Some(chunk) = stream.next().await {
+            let mut stream = match svc_res.take_body() {
+                ResponseBody::Body(b) => b,
+                ResponseBody::Other(_) => unreachable!("enable_response_buffering always sets ResponseBody::Body"),
+            };
+            while let Some(chunk) = next_body_chunk(&mut stream).await {
                 writer.write_all(&chunk?)?;
             }
 
             let jws_detached = writer.finish().map_err(|e| ErrorInternalServerError(e))?;
 
-            let mut svc_res = svc_res.map_body(|_, _| stream);
+            // The body was just drained to compute the signature; rewind it
+            // so the same buffered bytes are what actually gets sent back to
+            // the client.
+            stream.rewind();
+            let mut svc_res =
+                svc_res.map_body(|_, _| ResponseBody::Body(Body::from_message(stream)));
             svc_res.headers_mut().insert(
                 HeaderName::from_static("x-jws-signature"),
                 HeaderValue::from_bytes(&jws_detached).unwrap(),
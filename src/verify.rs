@@ -8,27 +8,63 @@ use std::{
 
 use actix_service::{Service, Transform};
 use actix_web::{
+    dev::Payload,
     dev::ServiceRequest,
     dev::{Body, ServiceResponse},
-    Error, HttpMessage,
+    error::ErrorForbidden,
+    Error, FromRequest, HttpMessage, HttpRequest,
 };
-use actix_web_buffering::{enable_request_buffering, FileBufferingStreamWrapper};
+use crate::buffering::FileBufferingStreamBuilder;
 use detached_jws::{DeserializeJwsWriter, JwsHeader, Verify};
-use futures::future::{ok, Future, Ready};
+use futures::future::{ok, ready, Future, LocalBoxFuture, Ready};
 use futures::{stream::StreamExt, FutureExt};
 
 pub enum VerifyErrorType {
     HeaderNotFound,
     IncorrectSignature,
+    DigestMismatch,
+    UnsupportedAlgorithm(String),
     Other(anyhow::Error),
 }
 
+/// The parsed JWS header of a request that passed `DetachedJwsVerify`,
+/// inserted into the request's extensions so handlers can learn who signed
+/// it (e.g. for per-key authorization or logging).
+#[derive(Clone)]
+pub struct VerifiedJws {
+    pub header: JwsHeader,
+    pub algorithm: String,
+    pub key_id: Option<String>,
+}
+
+impl FromRequest for VerifiedJws {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<VerifiedJws>()
+                .cloned()
+                .ok_or_else(|| ErrorForbidden("no verified detached JWS signature present")),
+        )
+    }
+}
+
 pub trait DetachedJwsVerifyConfig<'a> {
     type Verifier: Verify;
     type ErrorHandler: Future<Output = Error>;
 
     fn get_verifier(&'a self, h: &JwsHeader) -> Option<Self::Verifier>;
 
+    /// Decides whether a given request needs to carry a verified signature
+    /// at all. May inspect the (buffered, replayable) request body. Defaults
+    /// to always requiring verification.
+    fn should_verify(&'a self, _req: &'a mut ServiceRequest) -> LocalBoxFuture<'a, bool> {
+        ready(true).boxed_local()
+    }
+
     fn error_handler(
         &'a self,
         req: &'a mut ServiceRequest,
@@ -38,21 +74,43 @@ pub trait DetachedJwsVerifyConfig<'a> {
 
 pub struct DetachedJwsVerify<T> {
     config: Arc<T>,
-    buffering: Rc<FileBufferingStreamWrapper>,
+    buffering: Arc<FileBufferingStreamBuilder>,
 }
 
 impl<T> DetachedJwsVerify<T> {
     pub fn new(config: Arc<T>) -> Self {
         Self {
             config,
-            buffering: Rc::new(FileBufferingStreamWrapper::new()),
+            buffering: Arc::new(FileBufferingStreamBuilder::new()),
         }
     }
 
-    pub fn override_buffering(mut self, v: Rc<FileBufferingStreamWrapper>) -> Self {
+    pub fn override_buffering(mut self, v: Arc<FileBufferingStreamBuilder>) -> Self {
         self.buffering = v;
         self
     }
+
+    /// In-memory size (in bytes) the request body may reach before it spills
+    /// to a temp file.
+    pub fn buffering_threshold(self, v: usize) -> Self {
+        self.with_buffering(|b| b.threshold(v))
+    }
+
+    /// Directory used for spilled request bodies.
+    pub fn buffering_tmp_dir(self, v: impl AsRef<std::path::Path>) -> Self {
+        self.with_buffering(|b| b.tmp_dir(v))
+    }
+
+    /// Largest request body accepted; larger bodies are rejected before being
+    /// buffered.
+    pub fn max_body_size(self, v: usize) -> Self {
+        self.with_buffering(|b| b.buffer_limit(Some(v)))
+    }
+
+    fn with_buffering(mut self, f: impl FnOnce(FileBufferingStreamBuilder) -> FileBufferingStreamBuilder) -> Self {
+        self.buffering = Arc::new(f((*self.buffering).clone()));
+        self
+    }
 }
 
 impl<S, T> Transform<S> for DetachedJwsVerify<T>
@@ -71,7 +129,7 @@ where
         ok(Middleware {
             service: Rc::new(RefCell::new(service)),
             config: Arc::clone(&self.config),
-            buffering: Rc::clone(&self.buffering),
+            buffering: Arc::clone(&self.buffering),
         })
     }
 }
@@ -80,7 +138,7 @@ pub struct Middleware<S, T> {
     // This is special: We need this to avoid lifetime issues.
     service: Rc<RefCell<S>>,
     config: Arc<T>,
-    buffering: Rc<FileBufferingStreamWrapper>,
+    buffering: Arc<FileBufferingStreamBuilder>,
 }
 
 impl<S, T> Service for Middleware<S, T>
@@ -100,10 +158,30 @@ where
     fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
         let mut svc = self.service.clone();
         let config = self.config.clone();
+        let buffering = self.buffering.clone();
 
-        enable_request_buffering(&self.buffering, &mut req);
+        let mut stream = buffering.build(req.take_payload());
 
         async move {
+            // Drained once, up front, into a `BufferedBodyReader`: every
+            // later consumer (`should_verify`, the JWS writer, the
+            // downstream handler) gets its own independent clone with its
+            // own cursor, so none of them can steal bytes the others still
+            // need to see from the start.
+            while let Some(chunk) = stream.next().await {
+                chunk?;
+            }
+            let reader = stream
+                .into_reader()
+                .expect("stream was just drained to EOF above");
+
+            req.set_payload(Payload::Stream(reader.clone().boxed_local()));
+
+            if !config.should_verify(&mut req).await {
+                req.set_payload(Payload::Stream(reader.boxed_local()));
+                return svc.call(req).await;
+            }
+
             let jws = match req.headers().get("x-jws-signature") {
                 Some(h) => h,
                 None => {
@@ -122,12 +200,14 @@ where
                 }
             };
 
-            let mut stream = req.take_payload();
-            while let Some(chunk) = stream.next().await {
-                writer.write_all(&chunk?)?;
+            {
+                let mut verify_reader = reader.clone();
+                while let Some(chunk) = verify_reader.next().await {
+                    writer.write_all(&chunk?)?;
+                }
             }
 
-            let _ = match writer.finish() {
+            let header = match writer.finish() {
                 Ok(o) => o,
                 Err(_) => {
                     return Err(config
@@ -136,6 +216,21 @@ where
                 }
             };
 
+            let algorithm = header
+                .get("alg")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_owned();
+            let key_id = header.get("kid").and_then(|v| v.as_str()).map(str::to_owned);
+
+            req.extensions_mut().insert(VerifiedJws {
+                header,
+                algorithm,
+                key_id,
+            });
+
+            req.set_payload(Payload::Stream(reader.boxed_local()));
+
             svc.call(req).await
         }
         .boxed_local()
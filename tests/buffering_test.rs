@@ -0,0 +1,40 @@
+use actix_web::{error::PayloadError, web::Bytes};
+use actix_web_detached_jws_middleware::buffering::FileBufferingStreamBuilder;
+use futures::{stream, StreamExt};
+
+async fn drain(mut stream: impl futures::Stream<Item = Result<Bytes, PayloadError>> + Unpin) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        out.extend_from_slice(&chunk.unwrap());
+    }
+    out
+}
+
+#[actix_rt::test]
+async fn test_spilled_buffer_rewinds_and_replays() {
+    // A threshold far smaller than the payload forces the buffer to spill
+    // to a temp file on its very first write.
+    let builder = FileBufferingStreamBuilder::new().threshold(8);
+
+    let chunk_a = vec![1u8; 50];
+    let chunk_b = vec![2u8; 50];
+    let expected: Vec<u8> = chunk_a.iter().chain(chunk_b.iter()).copied().collect();
+
+    let inner = stream::iter(vec![
+        Ok::<_, PayloadError>(Bytes::from(chunk_a)),
+        Ok::<_, PayloadError>(Bytes::from(chunk_b)),
+    ]);
+    let mut buffered = builder.build(inner);
+
+    assert_eq!(drain(&mut buffered).await, expected);
+
+    // Rewinding replays the spilled file from the start rather than the
+    // (already exhausted) inner stream.
+    buffered.rewind();
+    assert_eq!(drain(&mut buffered).await, expected);
+
+    // `into_reader` tees off an independent handle over the same spilled
+    // file, with its own cursor.
+    let reader = buffered.into_reader().expect("inner stream reached eof");
+    assert_eq!(drain(reader).await, expected);
+}
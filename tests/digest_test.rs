@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use actix_service::{IntoService, Service, Transform};
+use actix_web::{
+    dev::ServiceRequest,
+    error::ErrorForbidden,
+    http::StatusCode,
+    test::{self, TestRequest},
+    Error, HttpMessage, HttpResponse,
+};
+use actix_web_detached_jws_middleware::{
+    DigestSign, DigestVerify, DigestVerifyConfig, FileBufferingStreamBuilder, VerifyErrorType,
+};
+use futures::future::{ready, Ready};
+
+struct Config;
+
+impl<'a> DigestVerifyConfig<'a> for Config {
+    type ErrorHandler = Ready<Error>;
+
+    fn error_handler(&'a self, _req: &'a mut ServiceRequest, error: VerifyErrorType) -> Self::ErrorHandler {
+        ready(match error {
+            VerifyErrorType::HeaderNotFound => ErrorForbidden("Header Not Found"),
+            VerifyErrorType::IncorrectSignature => ErrorForbidden("Incorrect Signature"),
+            VerifyErrorType::DigestMismatch => ErrorForbidden("Digest Mismatch"),
+            VerifyErrorType::UnsupportedAlgorithm(alg) => ErrorForbidden(format!("Unsupported Algorithm: {}", alg)),
+            VerifyErrorType::Other(e) => ErrorForbidden(e.to_string()),
+        })
+    }
+}
+
+#[actix_rt::test]
+async fn test_round_trip() {
+    let payload: Vec<u8> = (0..255).collect();
+
+    // Sign a response over the payload with DigestSign...
+    let sign_srv = |req: ServiceRequest| {
+        let body = req.extensions().get::<Vec<u8>>().cloned().unwrap();
+        futures::future::ok(req.into_response(HttpResponse::Ok().body(body)))
+    };
+
+    let mut sign_mw = DigestSign::new()
+        .new_transform(sign_srv.into_service())
+        .await
+        .unwrap();
+
+    let req = TestRequest::default().to_srv_request();
+    req.extensions_mut().insert(payload.clone());
+    let signed = test::call_service(&mut sign_mw, req).await;
+    let digest = signed.headers().get("digest").unwrap().to_str().unwrap().to_owned();
+
+    // ...and feed the resulting `Digest` header into DigestVerify alongside
+    // the same body.
+    let verify_srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let mut verify_mw = DigestVerify::new(Arc::new(Config))
+        .new_transform(verify_srv.into_service())
+        .await
+        .unwrap();
+
+    let resp = test::call_service(
+        &mut verify_mw,
+        TestRequest::default()
+            .header("Digest", digest)
+            .set_payload(payload)
+            .to_srv_request(),
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_rejects_mismatched_digest() {
+    let verify_srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let mut verify_mw = DigestVerify::new(Arc::new(Config))
+        .new_transform(verify_srv.into_service())
+        .await
+        .unwrap();
+
+    let result = verify_mw
+        .call(
+            TestRequest::default()
+                .header("Digest", format!("SHA-256={}", base64::encode([0u8; 32])))
+                .set_payload(vec![1, 2, 3])
+                .to_srv_request(),
+        )
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[actix_rt::test]
+async fn test_round_trip_spills_to_disk() {
+    let payload: Vec<u8> = (0..255).collect();
+
+    // A threshold far smaller than the 255-byte payload forces both
+    // `DigestSign`'s response buffering and `DigestVerify`'s request
+    // buffering to spill to a temp file.
+    let sign_srv = |req: ServiceRequest| {
+        let body = req.extensions().get::<Vec<u8>>().cloned().unwrap();
+        futures::future::ok(req.into_response(HttpResponse::Ok().body(body)))
+    };
+
+    let mut sign_mw = DigestSign::new()
+        .override_buffering(Arc::new(FileBufferingStreamBuilder::new().threshold(8)))
+        .new_transform(sign_srv.into_service())
+        .await
+        .unwrap();
+
+    let req = TestRequest::default().to_srv_request();
+    req.extensions_mut().insert(payload.clone());
+    let signed = test::call_service(&mut sign_mw, req).await;
+    let digest = signed.headers().get("digest").unwrap().to_str().unwrap().to_owned();
+    let body = test::read_body(signed).await;
+    assert_eq!(body.as_ref(), payload.as_slice());
+
+    let verify_srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let mut verify_mw = DigestVerify::new(Arc::new(Config))
+        .override_buffering(Arc::new(FileBufferingStreamBuilder::new().threshold(8)))
+        .new_transform(verify_srv.into_service())
+        .await
+        .unwrap();
+
+    let resp = test::call_service(
+        &mut verify_mw,
+        TestRequest::default()
+            .header("Digest", digest)
+            .set_payload(payload)
+            .to_srv_request(),
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
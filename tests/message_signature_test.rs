@@ -0,0 +1,163 @@
+#[macro_use]
+extern crate lazy_static;
+
+use std::sync::Arc;
+
+use actix_service::{IntoService, Service, Transform};
+use actix_web::{
+    dev::ServiceRequest,
+    error::ErrorForbidden,
+    http::{Method, StatusCode},
+    test::{self, TestRequest},
+    Error, HttpResponse,
+};
+use actix_web_detached_jws_middleware::{
+    MessageSignatureSign, MessageSignatureSignConfig, MessageSignatureVerify, MessageSignatureVerifyConfig,
+    VerifyErrorType,
+};
+use futures::future::{ready, Ready};
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::{Padding, Rsa},
+    sign::{Signer, Verifier},
+};
+
+struct SignConfig {
+    keypair: PKey<Private>,
+}
+
+impl<'a> MessageSignatureSignConfig<'a> for SignConfig {
+    type Signer = Signer<'a>;
+
+    fn get_signer(&'a self) -> (Self::Signer, String, Option<String>, Vec<String>) {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.keypair).unwrap();
+        signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+        (
+            signer,
+            "rsa-pss-sha256".to_owned(),
+            Some("k1".to_owned()),
+            vec!["(request-target)".to_owned(), "host".to_owned()],
+        )
+    }
+}
+
+struct VerifyConfig {
+    keypair: PKey<Private>,
+}
+
+impl<'a> MessageSignatureVerifyConfig<'a> for VerifyConfig {
+    type Verifier = Verifier<'a>;
+    type ErrorHandler = Ready<Error>;
+
+    fn get_verifier(&'a self, _key_id: Option<&str>, algorithm: &str) -> Option<Self::Verifier> {
+        match algorithm {
+            "rsa-pss-sha256" => {
+                let mut verifier = Verifier::new(MessageDigest::sha256(), &self.keypair).unwrap();
+                verifier.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+                Some(verifier)
+            }
+            _ => None,
+        }
+    }
+
+    fn error_handler(&'a self, _req: &'a mut ServiceRequest, error: VerifyErrorType) -> Self::ErrorHandler {
+        ready(match error {
+            VerifyErrorType::HeaderNotFound => ErrorForbidden("Header Not Found"),
+            VerifyErrorType::IncorrectSignature => ErrorForbidden("Incorrect Signature"),
+            VerifyErrorType::DigestMismatch => ErrorForbidden("Digest Mismatch"),
+            VerifyErrorType::UnsupportedAlgorithm(alg) => ErrorForbidden(format!("Unsupported Algorithm: {}", alg)),
+            VerifyErrorType::Other(e) => ErrorForbidden(e.to_string()),
+        })
+    }
+}
+
+#[actix_rt::test]
+async fn test_round_trip() {
+    lazy_static! {
+        static ref KEYPAIR: PKey<Private> = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    }
+
+    // Sign a response to a request over (request-target) and Host with
+    // MessageSignatureSign...
+    let sign_srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let mut sign_mw = MessageSignatureSign::new(Arc::new(SignConfig {
+        keypair: KEYPAIR.clone(),
+    }))
+    .new_transform(sign_srv.into_service())
+    .await
+    .unwrap();
+
+    let signed = test::call_service(
+        &mut sign_mw,
+        TestRequest::with_uri("/test")
+            .method(Method::GET)
+            .header("Host", "example.com")
+            .to_srv_request(),
+    )
+    .await;
+
+    let signature_input = signed.headers().get("signature-input").unwrap().to_str().unwrap().to_owned();
+    let signature = signed.headers().get("signature").unwrap().to_str().unwrap().to_owned();
+
+    // ...and feed the resulting `Signature-Input`/`Signature` headers into
+    // MessageSignatureVerify alongside a request with the same covered
+    // components.
+    let verify_srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let mut verify_mw = MessageSignatureVerify::new(Arc::new(VerifyConfig {
+        keypair: KEYPAIR.clone(),
+    }))
+    .new_transform(verify_srv.into_service())
+    .await
+    .unwrap();
+
+    let resp = test::call_service(
+        &mut verify_mw,
+        TestRequest::with_uri("/test")
+            .method(Method::GET)
+            .header("Host", "example.com")
+            .header("Signature-Input", signature_input)
+            .header("Signature", signature)
+            .to_srv_request(),
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_rejects_tampered_signature() {
+    lazy_static! {
+        static ref KEYPAIR_REJECT: PKey<Private> = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    }
+
+    let verify_srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let mut verify_mw = MessageSignatureVerify::new(Arc::new(VerifyConfig {
+        keypair: KEYPAIR_REJECT.clone(),
+    }))
+    .new_transform(verify_srv.into_service())
+    .await
+    .unwrap();
+
+    let result = verify_mw
+        .call(
+            TestRequest::with_uri("/test")
+                .method(Method::GET)
+                .header("Host", "example.com")
+                .header(
+                    "Signature-Input",
+                    "sig1=(\"(request-target)\" \"host\");keyid=\"k1\";alg=\"rsa-pss-sha256\"",
+                )
+                .header("Signature", format!("sig1=:{}:", base64::encode(vec![0u8; 256])))
+                .to_srv_request(),
+        )
+        .await;
+
+    assert!(result.is_err());
+}
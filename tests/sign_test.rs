@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use actix_service::{IntoService, Transform};
+use actix_web::{
+    dev::ServiceRequest,
+    test::{self, TestRequest},
+    HttpResponse,
+};
+use actix_web_detached_jws_middleware::{DetachedJwsSign, DetachedJwsSignConfig};
+use detached_jws::JwsHeader;
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::{Padding, Rsa},
+    sign::{Signer, Verifier},
+};
+
+struct Config {
+    keypair: PKey<Private>,
+}
+
+impl<'a> DetachedJwsSignConfig<'a> for Config {
+    type Signer = Signer<'a>;
+
+    fn get_signer(&'a self) -> (Self::Signer, String, JwsHeader) {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.keypair).unwrap();
+        signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+        (signer, "PS256".to_owned(), JwsHeader::new())
+    }
+}
+
+#[actix_rt::test]
+async fn test_round_trip_spills_response_to_disk() {
+    let keypair = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let payload: Vec<u8> = (0..255).collect();
+
+    let srv = {
+        let payload = payload.clone();
+        move |req: ServiceRequest| {
+            futures::future::ok(req.into_response(HttpResponse::Ok().body(payload.clone())))
+        }
+    };
+
+    // A threshold far smaller than the 255-byte body forces
+    // `enable_response_buffering` to spill the response to a temp file
+    // before `DetachedJwsSign` signs it.
+    let mut mw = DetachedJwsSign::new(Arc::new(Config {
+        keypair: keypair.clone(),
+    }))
+    .buffering_threshold(8)
+    .new_transform(srv.into_service())
+    .await
+    .unwrap();
+
+    let signed = test::call_service(&mut mw, TestRequest::default().to_srv_request()).await;
+    let jws = signed.headers().get("x-jws-signature").unwrap().to_str().unwrap().to_owned();
+
+    // The signed body must still be exactly what the handler produced...
+    let body = test::read_body(signed).await;
+    assert_eq!(body.as_ref(), payload.as_slice());
+
+    // ...and the detached signature must verify over that same body.
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &keypair).unwrap();
+    verifier.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+    detached_jws::deserialize(&jws, &mut payload.as_slice(), verifier).unwrap();
+}
@@ -1,24 +1,57 @@
 #[macro_use]
 extern crate lazy_static;
 
-use actix_service::{IntoService, Transform};
+use std::sync::Arc;
+
+use actix_service::{IntoService, Service, Transform};
 use actix_web::{
-    dev::{ServiceRequest, ServiceResponse},
-    http::{header::CONTENT_TYPE, HeaderValue, StatusCode},
-    middleware::errhandlers::{ErrorHandlerResponse, ErrorHandlers},
+    dev::ServiceRequest,
+    error::{ErrorForbidden, ErrorInternalServerError},
+    http::StatusCode,
     test::{self, TestRequest},
-    HttpResponse,
+    Error, HttpMessage, HttpResponse,
 };
-use actix_web_detached_jws_middleware::verify::DetachedJwsVerify;
+use actix_web_detached_jws_middleware::verify::{DetachedJwsVerify, DetachedJwsVerifyConfig, VerifyErrorType};
 use detached_jws::JwsHeader;
+use futures::future::{ready, LocalBoxFuture, Ready};
+use futures::{FutureExt, StreamExt};
 use openssl::{
     hash::MessageDigest,
-    pkey::PKey,
-    pkey::Private,
+    pkey::{PKey, Private},
     rsa::{Padding, Rsa},
     sign::{Signer, Verifier},
 };
 
+struct Config {
+    keypair: PKey<Private>,
+}
+
+impl<'a> DetachedJwsVerifyConfig<'a> for Config {
+    type Verifier = Verifier<'a>;
+    type ErrorHandler = Ready<Error>;
+
+    fn get_verifier(&'a self, h: &JwsHeader) -> Option<Self::Verifier> {
+        match h.get("alg")?.as_str()? {
+            "PS256" => {
+                let mut verifier = Verifier::new(MessageDigest::sha256(), &self.keypair).unwrap();
+                verifier.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+                Some(verifier)
+            }
+            _ => None,
+        }
+    }
+
+    fn error_handler(&'a self, _req: &'a mut ServiceRequest, error: VerifyErrorType) -> Self::ErrorHandler {
+        ready(match error {
+            VerifyErrorType::HeaderNotFound => ErrorForbidden("Header Not Found"),
+            VerifyErrorType::IncorrectSignature => ErrorForbidden("Incorrect Signature"),
+            VerifyErrorType::DigestMismatch => ErrorForbidden("Digest Mismatch"),
+            VerifyErrorType::UnsupportedAlgorithm(alg) => ErrorForbidden(format!("Unsupported Algorithm: {}", alg)),
+            VerifyErrorType::Other(e) => ErrorInternalServerError(e),
+        })
+    }
+}
+
 #[actix_rt::test]
 async fn test_handler() {
     lazy_static! {
@@ -42,15 +75,102 @@ async fn test_handler() {
     let srv =
         |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
 
-    let mut mw = DetachedJwsVerify::new(|_| -> Option<Verifier> {
-        let mut verifier = Verifier::new(MessageDigest::sha256(), &KEYPAIR_PS256).unwrap();
-        verifier.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
-        Some(verifier)
-    })
-    .new_transform(srv.into_service())
-    .await
+    let config = Arc::new(Config {
+        keypair: KEYPAIR_PS256.clone(),
+    });
+
+    let mut mw = DetachedJwsVerify::new(config)
+        .new_transform(srv.into_service())
+        .await
+        .unwrap();
+
+    let resp = test::call_service(
+        &mut mw,
+        TestRequest::default()
+            .header("X-JWS-Signature", jws)
+            .set_payload(payload)
+            .to_srv_request(),
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+/// A `should_verify` that reads the whole body before deciding, to prove
+/// doing so doesn't steal bytes the JWS check still needs.
+struct ShouldVerifyReadsBodyConfig {
+    keypair: PKey<Private>,
+}
+
+impl<'a> DetachedJwsVerifyConfig<'a> for ShouldVerifyReadsBodyConfig {
+    type Verifier = Verifier<'a>;
+    type ErrorHandler = Ready<Error>;
+
+    fn get_verifier(&'a self, h: &JwsHeader) -> Option<Self::Verifier> {
+        match h.get("alg")?.as_str()? {
+            "PS256" => {
+                let mut verifier = Verifier::new(MessageDigest::sha256(), &self.keypair).unwrap();
+                verifier.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+                Some(verifier)
+            }
+            _ => None,
+        }
+    }
+
+    fn should_verify(&'a self, req: &'a mut ServiceRequest) -> LocalBoxFuture<'a, bool> {
+        async move {
+            let mut payload = req.take_payload();
+            while let Some(chunk) = payload.next().await {
+                chunk.unwrap();
+            }
+            true
+        }
+        .boxed_local()
+    }
+
+    fn error_handler(&'a self, _req: &'a mut ServiceRequest, error: VerifyErrorType) -> Self::ErrorHandler {
+        ready(match error {
+            VerifyErrorType::HeaderNotFound => ErrorForbidden("Header Not Found"),
+            VerifyErrorType::IncorrectSignature => ErrorForbidden("Incorrect Signature"),
+            VerifyErrorType::DigestMismatch => ErrorForbidden("Digest Mismatch"),
+            VerifyErrorType::UnsupportedAlgorithm(alg) => ErrorForbidden(format!("Unsupported Algorithm: {}", alg)),
+            VerifyErrorType::Other(e) => ErrorInternalServerError(e),
+        })
+    }
+}
+
+#[actix_rt::test]
+async fn test_should_verify_reading_body_does_not_truncate_signature_check() {
+    lazy_static! {
+        static ref KEYPAIR_PS256_SHOULD_VERIFY: PKey<Private> =
+            PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    }
+
+    let mut signer = Signer::new(MessageDigest::sha256(), &KEYPAIR_PS256_SHOULD_VERIFY).unwrap();
+    signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+
+    let payload: Vec<u8> = (0..255).collect();
+
+    let jws = detached_jws::serialize(
+        "PS256".to_owned(),
+        JwsHeader::new(),
+        &mut payload.as_slice(),
+        signer,
+    )
     .unwrap();
 
+    let srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let config = Arc::new(ShouldVerifyReadsBodyConfig {
+        keypair: KEYPAIR_PS256_SHOULD_VERIFY.clone(),
+    });
+
+    let mut mw = DetachedJwsVerify::new(config)
+        .new_transform(srv.into_service())
+        .await
+        .unwrap();
+
     let resp = test::call_service(
         &mut mw,
         TestRequest::default()
@@ -59,4 +179,78 @@ async fn test_handler() {
             .to_srv_request(),
     )
     .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_rejects_missing_signature() {
+    lazy_static! {
+        static ref KEYPAIR_PS256_REJECT: PKey<Private> =
+            PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    }
+
+    let srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let config = Arc::new(Config {
+        keypair: KEYPAIR_PS256_REJECT.clone(),
+    });
+
+    let mut mw = DetachedJwsVerify::new(config)
+        .new_transform(srv.into_service())
+        .await
+        .unwrap();
+
+    let result = mw.call(TestRequest::default().to_srv_request()).await;
+
+    assert!(result.is_err());
+}
+
+#[actix_rt::test]
+async fn test_handler_spills_request_to_disk() {
+    lazy_static! {
+        static ref KEYPAIR_PS256_SPILL: PKey<Private> =
+            PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    }
+
+    let mut signer = Signer::new(MessageDigest::sha256(), &KEYPAIR_PS256_SPILL).unwrap();
+    signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+
+    let payload: Vec<u8> = (0..255).collect();
+
+    let jws = detached_jws::serialize(
+        "PS256".to_owned(),
+        JwsHeader::new(),
+        &mut payload.as_slice(),
+        signer,
+    )
+    .unwrap();
+
+    let srv =
+        |req: ServiceRequest| futures::future::ok(req.into_response(HttpResponse::Ok().finish()));
+
+    let config = Arc::new(Config {
+        keypair: KEYPAIR_PS256_SPILL.clone(),
+    });
+
+    // A threshold far smaller than the 255-byte body forces the buffer
+    // built in `Middleware::call` to spill the request to a temp file
+    // before it's hashed and replayed.
+    let mut mw = DetachedJwsVerify::new(config)
+        .buffering_threshold(8)
+        .new_transform(srv.into_service())
+        .await
+        .unwrap();
+
+    let resp = test::call_service(
+        &mut mw,
+        TestRequest::default()
+            .header("X-JWS-Signature", jws)
+            .set_payload(payload)
+            .to_srv_request(),
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
 }